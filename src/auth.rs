@@ -0,0 +1,126 @@
+//! Bearer-token authentication, modeled on kittybox's `tokenauth` module: a
+//! request guard reads `Authorization: Bearer <token>`, checks it against
+//! the set of issued tokens, and yields the caller's user id.
+
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::{Outcome, State};
+
+use uuid::Uuid;
+
+use persist::{atomic_write_json, load_json_or_default};
+
+pub type UserId = String;
+
+/// Tracks issued tokens and which user each one belongs to, persisted to a
+/// JSON file so a restart doesn't regenerate every token/user_id pair and
+/// strand the per-user todos a `FileStore` already flushed to disk.
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: Mutex<HashMap<String, UserId>>,
+}
+
+impl TokenStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<TokenStore> {
+        let path = path.into();
+        let tokens = load_json_or_default(&path)?;
+
+        Ok(TokenStore {
+            path,
+            tokens: Mutex::new(tokens),
+        })
+    }
+
+    fn persist(&self, tokens: &HashMap<String, UserId>) -> io::Result<()> {
+        atomic_write_json(&self.path, tokens)
+    }
+
+    /// Mints a token for a brand new user id and returns both.
+    pub fn issue(&self) -> (String, UserId) {
+        let token = Uuid::new_v4().to_hyphenated().to_string();
+        let user_id = Uuid::new_v4().to_hyphenated().to_string();
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(token.clone(), user_id.clone());
+        self.persist(&tokens).expect("failed to persist token store");
+        (token, user_id)
+    }
+
+    fn user_for(&self, token: &str) -> Option<UserId> {
+        self.tokens.lock().unwrap().get(token).cloned()
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthTokenError {
+    Missing,
+    Invalid,
+}
+
+/// Request guard yielding the caller's `UserId`, extracted from the
+/// `Authorization: Bearer <token>` header.
+pub struct AuthToken(pub UserId);
+
+impl<'a, 'r> FromRequest<'a, 'r> for AuthToken {
+    type Error = AuthTokenError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<AuthToken, AuthTokenError> {
+        let token = match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            Some(token) => token,
+            None => return Outcome::Failure((Status::Unauthorized, AuthTokenError::Missing)),
+        };
+
+        let token_store = match request.guard::<State<TokenStore>>() {
+            Outcome::Success(token_store) => token_store,
+            _ => return Outcome::Failure((Status::Unauthorized, AuthTokenError::Invalid)),
+        };
+
+        match token_store.user_for(token) {
+            Some(user_id) => Outcome::Success(AuthToken(user_id)),
+            None => Outcome::Failure((Status::Unauthorized, AuthTokenError::Invalid)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AdminTokenError {
+    Missing,
+    Invalid,
+}
+
+/// Request guard gating admin-only routes (like minting tokens) behind a
+/// separate secret, read from `Authorization: Bearer <token>` and compared
+/// against the `TODO_ADMIN_TOKEN` environment variable. Fails closed: if
+/// the variable isn't set, no bearer value can satisfy this guard.
+pub struct AdminToken;
+
+impl<'a, 'r> FromRequest<'a, 'r> for AdminToken {
+    type Error = AdminTokenError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<AdminToken, AdminTokenError> {
+        let provided = match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            Some(token) => token,
+            None => return Outcome::Failure((Status::Unauthorized, AdminTokenError::Missing)),
+        };
+
+        let expected = env::var("TODO_ADMIN_TOKEN").unwrap_or_default();
+        if !expected.is_empty() && provided == expected {
+            Outcome::Success(AdminToken)
+        } else {
+            Outcome::Failure((Status::Unauthorized, AdminTokenError::Invalid))
+        }
+    }
+}
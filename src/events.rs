@@ -0,0 +1,144 @@
+//! Fan-out of todo-list changes to any number of subscribed `/events`
+//! connections, so multiple tabs/devices watch the same list update live.
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use store::{Todo, Todos};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Default cap on concurrently open `/events` connections, overridable via
+/// `TODO_MAX_SSE_CONNECTIONS`. Rocket 0.4 reads a `Stream<Read>` body
+/// synchronously, so every open SSE connection parks one worker thread for
+/// as long as the connection stays up; left uncapped, a handful of
+/// subscribers can claim every worker in the pool and wedge the other
+/// routes. Rejecting new subscribers past this cap keeps workers free for
+/// everything else.
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TodoEvent {
+    Snapshot { todos: Todos },
+    Added { todo: Todo },
+    Removed { todo_id: String },
+    Updated { todo: Todo },
+    Toggled { todo: Todo },
+    ToggledAll,
+    Cleared,
+}
+
+/// Every subscriber gets its own channel, scoped to a user id so one
+/// account's changes never show up on another account's `/events` stream.
+/// `publish` fans a message out to all of that user's subscribers and
+/// drops any whose receiving end has gone away. Cloning shares the same
+/// underlying state, which lets an `EventStream` hold a handle past the
+/// lifetime of the request that created it, to release its connection
+/// slot when the stream ends.
+#[derive(Clone)]
+pub struct Broadcaster {
+    subscribers: Arc<Mutex<HashMap<String, Vec<Sender<String>>>>>,
+    active_connections: Arc<AtomicUsize>,
+    max_connections: usize,
+}
+
+impl Broadcaster {
+    pub fn new() -> Broadcaster {
+        let max_connections = env::var("TODO_MAX_SSE_CONNECTIONS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_CONNECTIONS);
+
+        Broadcaster {
+            subscribers: Arc::new(Mutex::new(HashMap::new())),
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            max_connections,
+        }
+    }
+
+    /// Registers a new subscriber, or returns `None` if `max_connections`
+    /// open `/events` streams are already claiming worker threads.
+    pub fn subscribe(&self, user_id: &str) -> Option<Receiver<String>> {
+        if self.active_connections.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+            self.active_connections.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        let (tx, rx) = channel();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(user_id.to_owned())
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Some(rx)
+    }
+
+    fn release_connection(&self) {
+        self.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn publish(&self, user_id: &str, event: &TodoEvent) {
+        let payload = match serde_json::to_string(event) {
+            Ok(payload) => payload,
+            Err(_) => return,
+        };
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(subscribers) = subscribers.get_mut(user_id) {
+            subscribers.retain(|tx| tx.send(payload.clone()).is_ok());
+        }
+    }
+}
+
+/// Adapts a `Receiver<String>` into a `Read` that yields a well-formed SSE
+/// byte stream, inserting a keep-alive comment whenever nothing has been
+/// published for a while so proxies don't time the connection out. Releases
+/// its `Broadcaster` connection slot on drop, once the client disconnects.
+pub struct EventStream {
+    rx: Receiver<String>,
+    buf: Vec<u8>,
+    broadcaster: Broadcaster,
+}
+
+impl EventStream {
+    pub fn new(rx: Receiver<String>, snapshot: Todos, broadcaster: Broadcaster) -> EventStream {
+        let snapshot_event = TodoEvent::Snapshot { todos: snapshot };
+        let payload = serde_json::to_string(&snapshot_event).unwrap_or_default();
+        EventStream {
+            rx,
+            buf: format!("data: {}\n\n", payload).into_bytes(),
+            broadcaster,
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.broadcaster.release_connection();
+    }
+}
+
+impl Read for EventStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.buf.is_empty() {
+            match self.rx.recv_timeout(KEEP_ALIVE_INTERVAL) {
+                Ok(payload) => self.buf = format!("data: {}\n\n", payload).into_bytes(),
+                Err(RecvTimeoutError::Timeout) => self.buf = b": keep-alive\n\n".to_vec(),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let n = out.len().min(self.buf.len());
+        out[..n].copy_from_slice(&self.buf[..n]);
+        self.buf.drain(..n);
+        Ok(n)
+    }
+}
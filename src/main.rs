@@ -3,14 +3,23 @@
 
 #[macro_use]
 extern crate rocket;
+extern crate pulldown_cmark;
 extern crate serde;
 extern crate serde_json;
 extern crate uuid;
 
+mod auth;
+mod events;
+mod markdown;
+mod persist;
+mod store;
+
 use std::path::{Path, PathBuf};
 
-use rocket::http::Method;
-use rocket::response::NamedFile;
+use rocket::http::{ContentType, Method, Status};
+use rocket::response::content::Content;
+use rocket::response::status::Custom;
+use rocket::response::{NamedFile, Stream};
 use rocket::State;
 
 use rocket_contrib::json::Json;
@@ -19,100 +28,37 @@ use rocket_cors::{AllowedHeaders, AllowedOrigins, Error};
 
 use serde::{Deserialize, Serialize};
 
-use uuid::Uuid;
+use auth::{AdminToken, AuthToken, TokenStore};
+use events::{Broadcaster, EventStream, TodoEvent};
+use store::{FileStore, OpResult, Todo, TodoOp, Todos, TodoStore};
 
-use std::sync::Mutex;
+static STATIC_DIRECTORY: &'static str = "static/";
+static TODOS_FILE: &'static str = "todos.json";
+static TOKENS_FILE: &'static str = "tokens.json";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Todo {
-    id: String,
-    content: String,
-    completed: bool,
+fn get_static_file(filename: PathBuf) -> PathBuf {
+    Path::new(STATIC_DIRECTORY).join(filename)
 }
 
-type Todos = Vec<Todo>;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct TodoAppState {
-    todos: Todos,
+fn find_todo(store: &dyn TodoStore, user_id: &str, todo_id: &str) -> Option<Todo> {
+    store
+        .list(user_id, None)
+        .into_iter()
+        .find(|todo| todo.id == todo_id)
 }
 
-impl TodoAppState {
-    fn new() -> TodoAppState {
-        TodoAppState { todos: vec![] }
-    }
-
-    fn add_todo(&mut self, content: &str) -> String {
-        let uuid = Uuid::new_v4();
-        let todo = Todo {
-            id: uuid.to_hyphenated().to_string(),
-            content: content.to_owned(),
-            completed: false,
-        };
-        let todo_id = todo.id.clone();
-        self.todos.push(todo);
-        todo_id
-    }
-
-    fn remove_todo(&mut self, todo_id: &str) -> bool {
-        let len = self.todos.len();
-        self.todos.retain(|todo| todo.id != todo_id);
-        len != self.todos.len()
-    }
-
-    fn clear_completed(&mut self) {
-        self.todos.retain(|todo| !todo.completed)
-    }
-
-    fn handle_todo<F>(&mut self, todo_id: &str, mut handler: F) -> bool
-    where
-        F: FnMut(&mut Todo) -> (),
-    {
-        for todo in &mut self.todos {
-            if todo.id == todo_id {
-                handler(todo);
-                return true;
-            }
-        }
-        return false;
-    }
-
-    fn update_todo(&mut self, todo_id: &str, todo_content: &str) -> bool {
-        self.handle_todo(todo_id, move |todo| {
-            *todo = Todo {
-                id: todo_id.to_owned(),
-                content: todo_content.to_owned(),
-                completed: todo.completed,
-            };
-        })
-    }
-
-    fn toggle_todo(&mut self, todo_id: &str) -> bool {
-        self.handle_todo(todo_id, |todo| {
-            todo.completed = !todo.completed;
-        })
-    }
-
-    fn toggle_all(&mut self) {
-        let mut all_completed = true;
-
-        for todo in &self.todos {
-            if !todo.completed {
-                all_completed = false;
-                break;
-            }
-        }
-
-        for todo in &mut self.todos {
-            (*todo).completed = !all_completed;
-        }
-    }
+#[derive(Serialize)]
+struct ErrorResponse {
+    success: bool,
+    message: String,
 }
 
-static STATIC_DIRECTORY: &'static str = "static/";
-
-fn get_static_file(filename: PathBuf) -> PathBuf {
-    Path::new(STATIC_DIRECTORY).join(filename)
+#[catch(401)]
+fn unauthorized() -> Json<ErrorResponse> {
+    Json(ErrorResponse {
+        success: false,
+        message: "missing or invalid bearer token".to_owned(),
+    })
 }
 
 #[get("/")]
@@ -120,6 +66,27 @@ fn index() -> Option<NamedFile> {
     NamedFile::open("index.html").ok()
 }
 
+#[derive(Serialize)]
+struct MintTokenResponse {
+    success: bool,
+    message: String,
+    token: String,
+}
+
+/// Mints a fresh bearer token bound to a brand new user id; callers pass
+/// the token back as `Authorization: Bearer <token>` on every other route.
+/// Gated behind `AdminToken` so anonymous callers can't mint unlimited
+/// accounts for themselves.
+#[post("/tokens")]
+fn mint_token(_admin: AdminToken, tokens: State<TokenStore>) -> Json<MintTokenResponse> {
+    let (token, _user_id) = tokens.issue();
+    Json(MintTokenResponse {
+        success: true,
+        message: "".to_owned(),
+        token,
+    })
+}
+
 #[derive(Serialize)]
 struct TodosResponse {
     success: bool,
@@ -128,22 +95,12 @@ struct TodosResponse {
 }
 
 #[get("/todos?<filter>")]
-fn todos(filter: Option<String>, state: State<Mutex<TodoAppState>>) -> Json<TodosResponse> {
-    let todo_app_state = state.lock().unwrap();
-    let mut todos = todo_app_state.todos.to_vec();
-
-    todos.retain(|todo| {
-        if let Some(filter) = &filter {
-            match &filter[..] {
-                "all" => true,
-                "active" => !todo.completed,
-                "completed" => todo.completed,
-                _ => true,
-            }
-        } else {
-            true
-        }
-    });
+fn todos(
+    filter: Option<String>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+) -> Json<TodosResponse> {
+    let todos = store.list(&auth.0, filter.as_deref());
 
     Json(TodosResponse {
         success: true,
@@ -152,6 +109,33 @@ fn todos(filter: Option<String>, state: State<Mutex<TodoAppState>>) -> Json<Todo
     })
 }
 
+/// Streams todo-list changes as Server-Sent Events. New subscribers get a
+/// `snapshot` event with the full current list first, so a freshly opened
+/// tab renders immediately without a separate `/todos` fetch. Rejected with
+/// 503 once `TODO_MAX_SSE_CONNECTIONS` streams are already open, so a burst
+/// of subscribers can't claim every worker thread and wedge other routes.
+#[get("/events")]
+fn events(
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
+) -> Result<Content<Stream<EventStream>>, Custom<Json<ErrorResponse>>> {
+    let rx = broadcaster.subscribe(&auth.0).ok_or_else(|| {
+        Custom(
+            Status::ServiceUnavailable,
+            Json(ErrorResponse {
+                success: false,
+                message: "too many open /events connections; try again shortly".to_owned(),
+            }),
+        )
+    })?;
+    let snapshot = store.list(&auth.0, None);
+    Ok(Content(
+        ContentType::new("text", "event-stream"),
+        Stream::from(EventStream::new(rx, snapshot, broadcaster.inner().clone())),
+    ))
+}
+
 #[derive(Deserialize)]
 struct AddTodoPayload {
     content: String,
@@ -166,15 +150,25 @@ struct AddTodoResponse {
 #[post("/add_todo", format = "json", data = "<payload>")]
 fn add_todo(
     payload: Json<AddTodoPayload>,
-    state: State<Mutex<TodoAppState>>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
 ) -> Json<AddTodoResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-    let response = AddTodoResponse {
+    let todo_id = store.add_todo(&auth.0, &payload.content);
+    broadcaster.publish(
+        &auth.0,
+        &TodoEvent::Added {
+            todo: Todo {
+                id: todo_id,
+                content: payload.content.clone(),
+                completed: false,
+            },
+        },
+    );
+    Json(AddTodoResponse {
         success: true,
         message: "".to_owned(),
-    };
-    todo_app_state.add_todo(&payload.content);
-    Json(response)
+    })
 }
 
 #[derive(Deserialize)]
@@ -191,15 +185,23 @@ struct RemoveTodoResponse {
 #[post("/remove_todo", format = "json", data = "<payload>")]
 fn remove_todo(
     payload: Json<RemoveTodoPayload>,
-    state: State<Mutex<TodoAppState>>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
 ) -> Json<RemoveTodoResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-
-    match todo_app_state.remove_todo(&payload.todo_id) {
-        true => Json(RemoveTodoResponse {
-            success: true,
-            message: "".to_owned(),
-        }),
+    match store.remove_todo(&auth.0, &payload.todo_id) {
+        true => {
+            broadcaster.publish(
+                &auth.0,
+                &TodoEvent::Removed {
+                    todo_id: payload.todo_id.clone(),
+                },
+            );
+            Json(RemoveTodoResponse {
+                success: true,
+                message: "".to_owned(),
+            })
+        }
         false => Json(RemoveTodoResponse {
             success: false,
             message: format!("{id} is not found", id = payload.todo_id),
@@ -222,14 +224,20 @@ struct UpdateTodoResponse {
 #[post("/update_todo", format = "json", data = "<payload>")]
 fn update_todo(
     payload: Json<UpdateTodoPayload>,
-    state: State<Mutex<TodoAppState>>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
 ) -> Json<UpdateTodoResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-    match todo_app_state.update_todo(&payload.todo_id, &payload.content) {
-        true => Json(UpdateTodoResponse {
-            success: true,
-            message: "".to_owned(),
-        }),
+    match store.update_todo(&auth.0, &payload.todo_id, &payload.content) {
+        true => {
+            if let Some(todo) = find_todo(&**store, &auth.0, &payload.todo_id) {
+                broadcaster.publish(&auth.0, &TodoEvent::Updated { todo });
+            }
+            Json(UpdateTodoResponse {
+                success: true,
+                message: "".to_owned(),
+            })
+        }
         false => Json(UpdateTodoResponse {
             success: false,
             message: format!("{id} is not found", id = &payload.todo_id),
@@ -251,14 +259,20 @@ struct ToggleTodoResponse {
 #[post("/toggle_todo", format = "json", data = "<payload>")]
 fn toggle_todo(
     payload: Json<ToggleTodoPayload>,
-    state: State<Mutex<TodoAppState>>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
 ) -> Json<ToggleTodoResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-    match todo_app_state.toggle_todo(&payload.todo_id) {
-        true => Json(ToggleTodoResponse {
-            success: true,
-            message: "".to_owned(),
-        }),
+    match store.toggle_todo(&auth.0, &payload.todo_id) {
+        true => {
+            if let Some(todo) = find_todo(&**store, &auth.0, &payload.todo_id) {
+                broadcaster.publish(&auth.0, &TodoEvent::Toggled { todo });
+            }
+            Json(ToggleTodoResponse {
+                success: true,
+                message: "".to_owned(),
+            })
+        }
         false => Json(ToggleTodoResponse {
             success: false,
             message: format!("{id} is not found!", id = payload.todo_id),
@@ -273,9 +287,13 @@ struct ClearCompletedResponse {
 }
 
 #[post("/clear_completed")]
-fn clear_completed(state: State<Mutex<TodoAppState>>) -> Json<ClearCompletedResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-    todo_app_state.clear_completed();
+fn clear_completed(
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
+) -> Json<ClearCompletedResponse> {
+    store.clear_completed(&auth.0);
+    broadcaster.publish(&auth.0, &TodoEvent::Cleared);
     Json(ClearCompletedResponse {
         success: true,
         message: "".to_owned(),
@@ -289,15 +307,107 @@ struct ToggleAllResponse {
 }
 
 #[post("/toggle_all")]
-fn toggle_all(state: State<Mutex<TodoAppState>>) -> Json<ToggleAllResponse> {
-    let mut todo_app_state = state.lock().unwrap();
-    todo_app_state.toggle_all();
+fn toggle_all(
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
+) -> Json<ToggleAllResponse> {
+    store.toggle_all(&auth.0);
+    broadcaster.publish(&auth.0, &TodoEvent::ToggledAll);
     Json(ToggleAllResponse {
         success: true,
         message: "".to_owned(),
     })
 }
 
+#[derive(Deserialize)]
+struct ImportMarkdownPayload {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct ImportMarkdownResponse {
+    success: bool,
+    message: String,
+    data: Todos,
+}
+
+#[post("/import_markdown", format = "json", data = "<payload>")]
+fn import_markdown(
+    payload: Json<ImportMarkdownPayload>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
+) -> Json<ImportMarkdownResponse> {
+    for todo in markdown::import(&payload.text) {
+        let todo_id = store.add_todo(&auth.0, &todo.content);
+        if todo.completed {
+            store.toggle_todo(&auth.0, &todo_id);
+        }
+        if let Some(todo) = find_todo(&**store, &auth.0, &todo_id) {
+            broadcaster.publish(&auth.0, &TodoEvent::Added { todo });
+        }
+    }
+
+    Json(ImportMarkdownResponse {
+        success: true,
+        message: "".to_owned(),
+        data: store.list(&auth.0, None),
+    })
+}
+
+#[get("/export_markdown")]
+fn export_markdown(auth: AuthToken, store: State<Box<dyn TodoStore>>) -> Content<String> {
+    Content(
+        ContentType::Plain,
+        markdown::export(&store.list(&auth.0, None)),
+    )
+}
+
+#[derive(Deserialize)]
+struct BatchPayload {
+    ops: Vec<TodoOp>,
+    #[serde(default)]
+    stop_on_error: bool,
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    success: bool,
+    message: String,
+    results: Vec<OpResult>,
+    data: Todos,
+}
+
+/// Applies an ordered batch of operations under a single lock acquisition,
+/// so a client that went offline can replay a queue of edits in one round
+/// trip instead of issuing N separate requests.
+#[post("/batch", format = "json", data = "<payload>")]
+fn batch(
+    payload: Json<BatchPayload>,
+    auth: AuthToken,
+    store: State<Box<dyn TodoStore>>,
+    broadcaster: State<Broadcaster>,
+) -> Json<BatchResponse> {
+    let payload = payload.into_inner();
+    let results = store.apply_batch(&auth.0, payload.ops, payload.stop_on_error);
+    let data = store.list(&auth.0, None);
+
+    broadcaster.publish(
+        &auth.0,
+        &TodoEvent::Snapshot {
+            todos: data.clone(),
+        },
+    );
+
+    Json(BatchResponse {
+        success: results.iter().all(|result| result.success),
+        message: "".to_owned(),
+        results,
+        data,
+    })
+}
+
 fn main() -> Result<(), Error> {
     let allowed_origins = AllowedOrigins::some_regex(&["^https?://localhost"]);
 
@@ -310,18 +420,30 @@ fn main() -> Result<(), Error> {
     .to_cors()?;
 
     let handlers = routes![
+        mint_token,
         todos,
+        events,
         add_todo,
         remove_todo,
         update_todo,
         toggle_todo,
         clear_completed,
-        toggle_all
+        toggle_all,
+        import_markdown,
+        export_markdown,
+        batch
     ];
+
+    let todo_store: Box<dyn TodoStore> =
+        Box::new(FileStore::new(TODOS_FILE).expect("failed to load todo store"));
+
     rocket::ignite()
         .mount("/", handlers)
         .mount("/static", StaticFiles::from(STATIC_DIRECTORY))
-        .manage(Mutex::new(TodoAppState::new()))
+        .register(catchers![unauthorized])
+        .manage(todo_store)
+        .manage(Broadcaster::new())
+        .manage(TokenStore::new(TOKENS_FILE).expect("failed to load token store"))
         .attach(cors)
         .launch();
 
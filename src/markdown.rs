@@ -0,0 +1,151 @@
+//! Round-trips the todo list through a GitHub-flavored Markdown task list,
+//! so a user can keep their list in a `TODO.md` and sync it with the app.
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use uuid::Uuid;
+
+use store::{Todo, Todos};
+
+/// Parses a GFM task list into todos. Nested task lists are recursed into
+/// (each list item tracks its own checkbox/text independently); items with
+/// no checkbox marker are skipped.
+pub fn import(text: &str) -> Todos {
+    let parser = Parser::new_ext(text, Options::all());
+    let mut todos = Todos::new();
+    let mut stack: Vec<Option<(bool, String)>> = Vec::new();
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::Item) => stack.push(None),
+            Event::TaskListMarker(done) => {
+                if let Some(top) = stack.last_mut() {
+                    *top = Some((done, String::new()));
+                }
+            }
+            Event::Text(text) => {
+                if let Some(Some((_, content))) = stack.last_mut() {
+                    content.push_str(&text);
+                }
+            }
+            Event::End(Tag::Item) => {
+                if let Some(Some((completed, content))) = stack.pop() {
+                    let content = content.trim().to_owned();
+                    if !content.is_empty() {
+                        todos.push(Todo {
+                            id: Uuid::new_v4().to_hyphenated().to_string(),
+                            content,
+                            completed,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    todos
+}
+
+/// Emits one `- [ ] content` / `- [x] content` line per todo, escaping
+/// Markdown-significant characters in `content`.
+pub fn export(todos: &Todos) -> String {
+    let mut output = String::new();
+
+    for todo in todos {
+        let marker = if todo.completed { "x" } else { " " };
+        // A raw newline would split `content` across Markdown list lines,
+        // letting reimport fabricate extra todos out of one line of text.
+        let content = todo.content.replace(['\r', '\n'], " ");
+        output.push_str(&format!("- [{}] {}\n", marker, escape(&content)));
+    }
+
+    output
+}
+
+/// Escapes what would actually change how the exported line re-parses: a
+/// leading list/heading marker (which would turn `content` into its own
+/// nested list item or heading), and `\`, `` ` ``, `[`, `]`, `*`, `_`, `~`
+/// anywhere (which open an escape, code span, link, or emphasis/strikethrough
+/// regardless of position — pulldown-cmark drops the delimiter characters
+/// from the parsed text once it pairs them up). A leading ordered-list
+/// marker (a digit run followed by `.` or `)`, e.g. `"1. "` or `"2) "`) would
+/// otherwise let pulldown-cmark open a nested ordered list right after the
+/// checkbox with no newline needed, and `import()` would then discard the
+/// outer item as empty — silently dropping the whole todo rather than just
+/// mangling it. The `.`/`)` after the digits is escaped rather than the
+/// first digit: `\` in front of a digit isn't a CommonMark escape and would
+/// survive literally on reimport, while `\.` and `\)` are, so the line no
+/// longer holds a list marker and re-parses to the original text.
+fn escape(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    let ordered_delimiter = if starts_with_ordered_marker(content) {
+        content.find(|ch: char| !ch.is_ascii_digit())
+    } else {
+        None
+    };
+
+    if let Some(first) = content.chars().next() {
+        if "-+#>".contains(first) {
+            escaped.push('\\');
+        }
+    }
+
+    for (index, ch) in content.char_indices() {
+        if "\\`[]*_~".contains(ch) || Some(index) == ordered_delimiter {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+
+    escaped
+}
+
+/// True if `content` starts with a CommonMark ordered-list marker: one or
+/// more ASCII digits immediately followed by `.` or `)`.
+fn starts_with_ordered_marker(content: &str) -> bool {
+    let digits_end = content
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(content.len());
+
+    digits_end > 0 && matches!(content[digits_end..].chars().next(), Some('.') | Some(')'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(content: &str, completed: bool) -> Todo {
+        Todo {
+            id: Uuid::new_v4().to_hyphenated().to_string(),
+            content: content.to_owned(),
+            completed,
+        }
+    }
+
+    fn round_trip(content: &str) -> Todos {
+        import(&export(&vec![todo(content, false)]))
+    }
+
+    #[test]
+    fn round_trips_plain_text() {
+        let todos = round_trip("buy milk");
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].content, "buy milk");
+    }
+
+    #[test]
+    fn round_trips_leading_ordered_list_markers() {
+        for content in ["1. Buy milk", "2) stuff", "10. ten things"] {
+            let todos = round_trip(content);
+            assert_eq!(todos.len(), 1, "dropped todo for {:?}", content);
+            assert_eq!(todos[0].content, content);
+        }
+    }
+
+    #[test]
+    fn leaves_bare_leading_digits_unescaped_by_marker_rule() {
+        // No `.`/`)` after the digit run, so this never looked like an
+        // ordered-list marker in the first place.
+        assert!(!starts_with_ordered_marker("2 apples"));
+    }
+}
@@ -0,0 +1,32 @@
+//! Shared crash-recovery primitives for the JSON-file-backed stores
+//! (`TokenStore`, `FileStore`): load-or-default on startup, and
+//! write-to-temp-file-then-rename on every save.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Loads `path` as JSON, or returns `T::default()` if it doesn't exist yet
+/// (e.g. first run).
+pub fn load_json_or_default<T: Default + DeserializeOwned>(path: &Path) -> io::Result<T> {
+    if path.exists() {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// Write-to-temp-file-then-rename so a reader never observes a half-written
+/// file, even if two requests race to persist.
+pub fn atomic_write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let serialized =
+        serde_json::to_string(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    fs::write(&tmp_path, serialized)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
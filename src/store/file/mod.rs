@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use persist::{atomic_write_json, load_json_or_default};
+
+use super::{OpResult, TodoAppState, TodoOp, TodoStore, Todos};
+
+/// A JSON-file-backed store, keyed by user id. The whole map is loaded into
+/// memory on startup and flushed after every mutation, so restarting the
+/// process doesn't lose anything.
+pub struct FileStore {
+    path: PathBuf,
+    users: Mutex<HashMap<String, TodoAppState>>,
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<FileStore> {
+        let path = path.into();
+        let users = load_json_or_default(&path)?;
+
+        Ok(FileStore {
+            path,
+            users: Mutex::new(users),
+        })
+    }
+
+    fn persist(&self, users: &HashMap<String, TodoAppState>) -> io::Result<()> {
+        atomic_write_json(&self.path, users)
+    }
+}
+
+impl TodoStore for FileStore {
+    fn list(&self, user_id: &str, filter: Option<&str>) -> Todos {
+        let users = self.users.lock().unwrap();
+        match users.get(user_id) {
+            Some(state) => state.list(filter),
+            None => Todos::new(),
+        }
+    }
+
+    fn add_todo(&self, user_id: &str, content: &str) -> String {
+        let mut users = self.users.lock().unwrap();
+        let todo_id = users
+            .entry(user_id.to_owned())
+            .or_default()
+            .add_todo(content);
+        self.persist(&users).expect("failed to persist todo store");
+        todo_id
+    }
+
+    fn remove_todo(&self, user_id: &str, todo_id: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        let removed = users
+            .entry(user_id.to_owned())
+            .or_default()
+            .remove_todo(todo_id);
+        if removed {
+            self.persist(&users).expect("failed to persist todo store");
+        }
+        removed
+    }
+
+    fn update_todo(&self, user_id: &str, todo_id: &str, content: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        let updated = users
+            .entry(user_id.to_owned())
+            .or_default()
+            .update_todo(todo_id, content);
+        if updated {
+            self.persist(&users).expect("failed to persist todo store");
+        }
+        updated
+    }
+
+    fn toggle_todo(&self, user_id: &str, todo_id: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        let toggled = users
+            .entry(user_id.to_owned())
+            .or_default()
+            .toggle_todo(todo_id);
+        if toggled {
+            self.persist(&users).expect("failed to persist todo store");
+        }
+        toggled
+    }
+
+    fn toggle_all(&self, user_id: &str) {
+        let mut users = self.users.lock().unwrap();
+        users.entry(user_id.to_owned()).or_default().toggle_all();
+        self.persist(&users).expect("failed to persist todo store");
+    }
+
+    fn clear_completed(&self, user_id: &str) {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .clear_completed();
+        self.persist(&users).expect("failed to persist todo store");
+    }
+
+    fn apply_batch(&self, user_id: &str, ops: Vec<TodoOp>, stop_on_error: bool) -> Vec<OpResult> {
+        let mut users = self.users.lock().unwrap();
+        let results = users
+            .entry(user_id.to_owned())
+            .or_default()
+            .apply_batch(ops, stop_on_error);
+        self.persist(&users).expect("failed to persist todo store");
+        results
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{OpResult, TodoAppState, TodoOp, TodoStore, Todos};
+
+/// The original in-process backend: todos live in a `Mutex` and are gone
+/// when the process exits. Each user id gets its own `TodoAppState`,
+/// created lazily on first use.
+pub struct MemoryStore {
+    users: Mutex<HashMap<String, TodoAppState>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl TodoStore for MemoryStore {
+    fn list(&self, user_id: &str, filter: Option<&str>) -> Todos {
+        let mut users = self.users.lock().unwrap();
+        users.entry(user_id.to_owned()).or_default().list(filter)
+    }
+
+    fn add_todo(&self, user_id: &str, content: &str) -> String {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .add_todo(content)
+    }
+
+    fn remove_todo(&self, user_id: &str, todo_id: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .remove_todo(todo_id)
+    }
+
+    fn update_todo(&self, user_id: &str, todo_id: &str, content: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .update_todo(todo_id, content)
+    }
+
+    fn toggle_todo(&self, user_id: &str, todo_id: &str) -> bool {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .toggle_todo(todo_id)
+    }
+
+    fn toggle_all(&self, user_id: &str) {
+        let mut users = self.users.lock().unwrap();
+        users.entry(user_id.to_owned()).or_default().toggle_all()
+    }
+
+    fn clear_completed(&self, user_id: &str) {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .clear_completed()
+    }
+
+    fn apply_batch(&self, user_id: &str, ops: Vec<TodoOp>, stop_on_error: bool) -> Vec<OpResult> {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user_id.to_owned())
+            .or_default()
+            .apply_batch(ops, stop_on_error)
+    }
+}
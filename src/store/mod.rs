@@ -0,0 +1,254 @@
+//! Persistence backends for the todo list.
+//!
+//! `TodoStore` is the extension point: routes only ever see
+//! `State<Box<dyn TodoStore>>`, so a new backend (sqlite, postgres, ...) can
+//! be dropped in by implementing the trait, without touching the route
+//! handlers in `main.rs`.
+
+mod file;
+mod memory;
+
+pub use file::FileStore;
+pub use memory::MemoryStore;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Todo {
+    pub id: String,
+    pub content: String,
+    pub completed: bool,
+}
+
+pub type Todos = Vec<Todo>;
+
+/// The plain data held by every backend; mutation logic lives here so
+/// `MemoryStore` and `FileStore` only need to add locking and (for the
+/// latter) persistence around it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TodoAppState {
+    todos: Todos,
+}
+
+impl TodoAppState {
+    fn list(&self, filter: Option<&str>) -> Todos {
+        let mut todos = self.todos.to_vec();
+
+        todos.retain(|todo| match filter {
+            Some("active") => !todo.completed,
+            Some("completed") => todo.completed,
+            _ => true,
+        });
+
+        todos
+    }
+
+    fn add_todo(&mut self, content: &str) -> String {
+        let uuid = Uuid::new_v4();
+        let todo = Todo {
+            id: uuid.to_hyphenated().to_string(),
+            content: content.to_owned(),
+            completed: false,
+        };
+        let todo_id = todo.id.clone();
+        self.todos.push(todo);
+        todo_id
+    }
+
+    fn remove_todo(&mut self, todo_id: &str) -> bool {
+        let len = self.todos.len();
+        self.todos.retain(|todo| todo.id != todo_id);
+        len != self.todos.len()
+    }
+
+    fn clear_completed(&mut self) {
+        self.todos.retain(|todo| !todo.completed)
+    }
+
+    fn handle_todo<F>(&mut self, todo_id: &str, mut handler: F) -> bool
+    where
+        F: FnMut(&mut Todo) -> (),
+    {
+        for todo in &mut self.todos {
+            if todo.id == todo_id {
+                handler(todo);
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn update_todo(&mut self, todo_id: &str, todo_content: &str) -> bool {
+        self.handle_todo(todo_id, move |todo| {
+            *todo = Todo {
+                id: todo_id.to_owned(),
+                content: todo_content.to_owned(),
+                completed: todo.completed,
+            };
+        })
+    }
+
+    fn toggle_todo(&mut self, todo_id: &str) -> bool {
+        self.handle_todo(todo_id, |todo| {
+            todo.completed = !todo.completed;
+        })
+    }
+
+    fn toggle_all(&mut self) {
+        let mut all_completed = true;
+
+        for todo in &self.todos {
+            if !todo.completed {
+                all_completed = false;
+                break;
+            }
+        }
+
+        for todo in &mut self.todos {
+            (*todo).completed = !all_completed;
+        }
+    }
+
+    /// Applies an ordered batch of operations under whatever lock the
+    /// caller already holds, reusing the same mutation methods a single
+    /// request would call. When `stop_on_error` is set, every op after the
+    /// first failure is skipped, and if any op failed the whole batch is
+    /// rolled back to how it looked before the batch started — so the
+    /// batch is all-or-nothing rather than leaving partial edits applied.
+    fn apply_batch(&mut self, ops: Vec<TodoOp>, stop_on_error: bool) -> Vec<OpResult> {
+        let snapshot = if stop_on_error {
+            Some(self.todos.clone())
+        } else {
+            None
+        };
+        let mut results = Vec::with_capacity(ops.len());
+        let mut failed = false;
+
+        for op in ops {
+            if stop_on_error && failed {
+                results.push(OpResult {
+                    success: false,
+                    message: "skipped after a previous operation failed".to_owned(),
+                });
+                continue;
+            }
+
+            let result = match op {
+                TodoOp::Add { content } => {
+                    self.add_todo(&content);
+                    OpResult {
+                        success: true,
+                        message: "".to_owned(),
+                    }
+                }
+                TodoOp::Remove { todo_id } => {
+                    if self.remove_todo(&todo_id) {
+                        OpResult {
+                            success: true,
+                            message: "".to_owned(),
+                        }
+                    } else {
+                        OpResult {
+                            success: false,
+                            message: format!("{id} is not found", id = todo_id),
+                        }
+                    }
+                }
+                TodoOp::Update { todo_id, content } => {
+                    if self.update_todo(&todo_id, &content) {
+                        OpResult {
+                            success: true,
+                            message: "".to_owned(),
+                        }
+                    } else {
+                        OpResult {
+                            success: false,
+                            message: format!("{id} is not found", id = todo_id),
+                        }
+                    }
+                }
+                TodoOp::Toggle { todo_id } => {
+                    if self.toggle_todo(&todo_id) {
+                        OpResult {
+                            success: true,
+                            message: "".to_owned(),
+                        }
+                    } else {
+                        OpResult {
+                            success: false,
+                            message: format!("{id} is not found!", id = todo_id),
+                        }
+                    }
+                }
+                TodoOp::ToggleAll => {
+                    self.toggle_all();
+                    OpResult {
+                        success: true,
+                        message: "".to_owned(),
+                    }
+                }
+                TodoOp::ClearCompleted => {
+                    self.clear_completed();
+                    OpResult {
+                        success: true,
+                        message: "".to_owned(),
+                    }
+                }
+            };
+
+            failed = failed || !result.success;
+            results.push(result);
+        }
+
+        if failed {
+            if let Some(snapshot) = snapshot {
+                self.todos = snapshot;
+                // The ops that ran before the failure really did succeed
+                // against `self.todos`, but that state no longer exists —
+                // say so, or a caller would think those edits stuck.
+                for result in &mut results {
+                    if result.success {
+                        result.success = false;
+                        result.message = "rolled back".to_owned();
+                    }
+                }
+            }
+        }
+
+        results
+    }
+}
+
+/// One entry of a `/batch` request, tagged by `op`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TodoOp {
+    Add { content: String },
+    Remove { todo_id: String },
+    Update { todo_id: String, content: String },
+    Toggle { todo_id: String },
+    ToggleAll,
+    ClearCompleted,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpResult {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Mirrors the inherent methods `TodoAppState` used to expose directly, so a
+/// route handler can work against any backend behind `Box<dyn TodoStore>`.
+/// Every method is scoped to a `user_id` so each authenticated caller only
+/// sees and mutates their own todos.
+pub trait TodoStore: Send + Sync {
+    fn list(&self, user_id: &str, filter: Option<&str>) -> Todos;
+    fn add_todo(&self, user_id: &str, content: &str) -> String;
+    fn remove_todo(&self, user_id: &str, todo_id: &str) -> bool;
+    fn update_todo(&self, user_id: &str, todo_id: &str, content: &str) -> bool;
+    fn toggle_todo(&self, user_id: &str, todo_id: &str) -> bool;
+    fn toggle_all(&self, user_id: &str);
+    fn clear_completed(&self, user_id: &str);
+    fn apply_batch(&self, user_id: &str, ops: Vec<TodoOp>, stop_on_error: bool) -> Vec<OpResult>;
+}